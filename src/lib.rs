@@ -0,0 +1,793 @@
+//! Library for decoding and encoding compact binary (CBOR) data.
+//!
+//! The two entry points most callers want are [`cbor_to_json`] and
+//! [`json_to_cbor`]. The lower-level `decode`/`encode` functions and the
+//! base64/multibase/armor helpers are also public for callers (and the
+//! `cbd` binary) that need the extra knobs, such as `--no-escape`.
+
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use ciborium::from_reader;
+use ciborium::into_writer;
+use ciborium::value::Integer;
+use base64::engine::general_purpose;
+use base64::Engine;
+use serde_json::json;
+
+/// Errors produced by the CBOR<->JSON conversion pipeline.
+///
+/// Marked `#[non_exhaustive]` so new failure kinds can be added without a
+/// breaking change; match with a wildcard arm.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CbdError {
+    /// Input that was expected to be text (base64, multibase, armor) wasn't
+    /// valid UTF-8.
+    InvalidUtf8(std::str::Utf8Error),
+    /// None of the supported base64 alphabets could decode the input.
+    InvalidBase64(base64::DecodeError),
+    /// A CBOR value failed to decode or encode.
+    Cbor(String),
+    /// A JSON value failed to parse or serialize.
+    Json(serde_json::Error),
+    /// Reading from or writing to the underlying stream failed.
+    Io(std::io::Error),
+}
+
+impl Display for CbdError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CbdError::InvalidUtf8(e) => write!(f, "Failed to decode input as utf8: {}", e),
+            CbdError::InvalidBase64(e) => write!(f, "Failed to decode base64: {}", e),
+            CbdError::Cbor(message) => write!(f, "{}", message),
+            CbdError::Json(e) => write!(f, "Failed to encode JSON: {}", e),
+            CbdError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CbdError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CbdError::InvalidUtf8(e) => Some(e),
+            CbdError::InvalidBase64(e) => Some(e),
+            CbdError::Cbor(_) => None,
+            CbdError::Json(e) => Some(e),
+            CbdError::Io(e) => Some(e),
+        }
+    }
+}
+
+/// The bases `--base` can emit, each written with its multibase prefix
+/// character (see <https://github.com/multiformats/multibase>).
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum Base {
+    Hex,
+    Base32,
+    Base58btc,
+    Base64url,
+    Base64,
+}
+
+const ARMOR_BEGIN: &str = "-----BEGIN CBOR-----";
+const ARMOR_END: &str = "-----END CBOR-----";
+const ARMOR_LINE_WIDTH: usize = 64;
+
+/// Decode CBOR bytes (optionally base64, multibase, or armor wrapped) to a
+/// JSON string, escaping byte strings, tags and non-finite floats into
+/// reserved objects that [`json_to_cbor`] rebuilds. Non-text map keys are
+/// written as their JSON text and integers outside the `i64`/`u64` range
+/// as floats; [`json_to_cbor`] does not restore either.
+pub fn cbor_to_json(input: &[u8]) -> Result<String, CbdError> {
+    decode(input, false)
+}
+
+/// Encode a JSON string to CBOR bytes, recognizing the reserved escape
+/// objects produced by [`cbor_to_json`].
+pub fn json_to_cbor(json: &str) -> Result<Vec<u8>, CbdError> {
+    encode(json, false)
+}
+
+pub fn try_base64_decode(input: &[u8]) -> Result<Vec<u8>, CbdError> {
+    let text = std::str::from_utf8(input)
+        .map_err(CbdError::InvalidUtf8)?
+        .trim_end();
+    if let Ok(bytes) = general_purpose::URL_SAFE_NO_PAD.decode(text) {
+        return Ok(bytes);
+    }
+    if let Ok(bytes) = general_purpose::STANDARD.decode(text) {
+        return Ok(bytes);
+    }
+    if let Ok(bytes) = general_purpose::URL_SAFE.decode(text) {
+        return Ok(bytes);
+    }
+    general_purpose::STANDARD_NO_PAD
+        .decode(text)
+        .map_err(CbdError::InvalidBase64)
+}
+
+pub fn base64_encode(input: &[u8]) -> String {
+    general_purpose::URL_SAFE_NO_PAD.encode(input)
+}
+
+/**
+ * Wrap CBOR bytes in a PEM-like armored container: base64 body, wrapped at
+ * 64 columns, between `-----BEGIN CBOR-----` / `-----END CBOR-----` lines.
+ */
+pub fn armor_encode(input: &[u8]) -> String {
+    let body = general_purpose::STANDARD.encode(input);
+    let mut armored = String::new();
+    armored.push_str(ARMOR_BEGIN);
+    armored.push('\n');
+    for line in body.as_bytes().chunks(ARMOR_LINE_WIDTH) {
+        armored.push_str(std::str::from_utf8(line).unwrap());
+        armored.push('\n');
+    }
+    armored.push_str(ARMOR_END);
+    armored.push('\n');
+    armored
+}
+
+/**
+ * Decode a PEM-like armored container: find the BEGIN/END markers, drop an
+ * optional `Key: Value` header block (everything up to the first blank
+ * line), and base64-decode the remaining body lines.
+ */
+pub fn try_armor_decode(input: &[u8]) -> Option<Vec<u8>> {
+    let text = std::str::from_utf8(input).ok()?;
+    let body_start = text.find(ARMOR_BEGIN)? + ARMOR_BEGIN.len();
+    let body_end = text[body_start..].find(ARMOR_END)? + body_start;
+    let inner = text[body_start..body_end].trim_start_matches('\n');
+
+    let has_header = inner.lines().any(|line| line.trim().is_empty());
+    let body_lines: Vec<&str> = if has_header {
+        inner
+            .lines()
+            .skip_while(|line| !line.trim().is_empty())
+            .skip(1)
+            .collect()
+    } else {
+        inner.lines().collect()
+    };
+    let body: String = body_lines.iter().map(|line| line.trim()).collect();
+
+    general_purpose::STANDARD
+        .decode(&body)
+        .or_else(|_| general_purpose::STANDARD_NO_PAD.decode(&body))
+        .ok()
+}
+
+/// Encode CBOR bytes as multibase-prefixed text in the requested base.
+pub fn multibase_encode(input: &[u8], base: Base) -> String {
+    match base {
+        Base::Hex => format!("f{}", hex::encode(input)),
+        Base::Base32 => format!(
+            "b{}",
+            base32::encode(base32::Alphabet::RFC4648 { padding: false }, input).to_lowercase()
+        ),
+        Base::Base58btc => format!("z{}", bs58::encode(input).into_string()),
+        Base::Base64url => format!("u{}", general_purpose::URL_SAFE_NO_PAD.encode(input)),
+        Base::Base64 => format!("m{}", general_purpose::STANDARD_NO_PAD.encode(input)),
+    }
+}
+
+/**
+ * Decode input carrying a multibase prefix character (`m` base64, `u`
+ * base64url, `f` base16, `b` base32, `z` base58btc). Returns `None` if the
+ * input isn't valid UTF-8, doesn't start with a recognized multibase code,
+ * or fails to decode under that base.
+ */
+pub fn try_multibase_decode(input: &[u8]) -> Option<Vec<u8>> {
+    let text = std::str::from_utf8(input).ok()?.trim_end();
+    let mut chars = text.chars();
+    let prefix = chars.next()?;
+    let rest = chars.as_str();
+    match prefix {
+        'm' => general_purpose::STANDARD_NO_PAD.decode(rest).ok(),
+        'u' => general_purpose::URL_SAFE_NO_PAD.decode(rest).ok(),
+        'f' => hex::decode(rest).ok(),
+        'b' => base32::decode(base32::Alphabet::RFC4648 { padding: false }, rest),
+        'z' => bs58::decode(rest).into_vec().ok(),
+        _ => None,
+    }
+}
+
+/// Whether `bytes` starts with a well-formed CBOR item, used to disambiguate
+/// candidate decodings that happen to both be syntactically valid under
+/// their respective alphabet but aren't both CBOR.
+fn looks_like_cbor(bytes: &[u8]) -> bool {
+    from_reader::<ciborium::Value, _>(bytes).is_ok()
+}
+
+/**
+ * Strip whichever wrapping the front door recognizes — an armored
+ * container, a multibase-prefixed encoding, or plain base64 — falling back
+ * to treating the input as raw CBOR.
+ */
+fn locate_cbor_bytes(input: &[u8]) -> Vec<u8> {
+    if let Some(cbor) = try_armor_decode(input) {
+        return cbor;
+    }
+    // A multibase prefix (`m`, `u`, `f`, `b`, `z`, ...) is an explicit,
+    // unambiguous declaration of the encoding, so prefer it whenever it
+    // decodes to valid CBOR. Several of those prefix characters are
+    // themselves valid base64url characters, so a base64-encoded input can
+    // be mistaken for a multibase one (and vice versa); only fall back to
+    // plain base64 — the tool's original, primary format — when the
+    // multibase candidate doesn't look like CBOR.
+    let multibase = try_multibase_decode(input);
+    let base64 = try_base64_decode(input).ok();
+    if let Some(cbor) = multibase.as_ref().filter(|cbor| looks_like_cbor(cbor)) {
+        return cbor.clone();
+    }
+    if let Some(cbor) = base64.as_ref().filter(|cbor| looks_like_cbor(cbor)) {
+        return cbor.clone();
+    }
+    if let Some(cbor) = base64 {
+        return cbor;
+    }
+    if let Some(cbor) = multibase {
+        return cbor;
+    }
+    input.to_vec()
+}
+
+/**
+ * Decode input to JSON
+ * Try an armored container first, then a multibase-prefixed encoding, then
+ * plain base64, then raw cbor
+ */
+pub fn decode(input: &[u8], no_escape: bool) -> Result<String, CbdError> {
+    try_cbor2json(&locate_cbor_bytes(input), no_escape)
+}
+
+/**
+ * Decode input to RFC 8949 extended diagnostic notation, which is strictly
+ * more expressive than JSON: byte strings as `h'..'`, tags as `N(value)`,
+ * and maps that preserve non-string keys.
+ *
+ * Unassigned CBOR simple values (e.g. `simple(255)`) are not supported:
+ * ciborium's `Value` can't represent them, so such input returns a
+ * `CbdError::Cbor` instead of the `simple(n)` notation RFC 8949 defines.
+ */
+pub fn decode_diag(input: &[u8]) -> Result<String, CbdError> {
+    let cbor = locate_cbor_bytes(input);
+    let value: ciborium::Value = from_reader(&cbor[..])
+        .map_err(|e| CbdError::Cbor(format!("Failed to decode CBOR: {}", e)))?;
+    let mut out = String::new();
+    diag(&value, &mut out);
+    Ok(out)
+}
+
+/// Recursive formatter that writes `value` in RFC 8949 diagnostic notation.
+fn diag(value: &ciborium::Value, out: &mut String) {
+    match value {
+        ciborium::Value::Integer(i) => out.push_str(&i128::from(*i).to_string()),
+        ciborium::Value::Bytes(bytes) => {
+            out.push_str("h'");
+            for byte in bytes {
+                out.push_str(&format!("{:02x}", byte));
+            }
+            out.push('\'');
+        }
+        ciborium::Value::Float(f) => {
+            if f.is_nan() {
+                out.push_str("NaN");
+            } else if f.is_infinite() {
+                out.push_str(if f.is_sign_positive() { "Infinity" } else { "-Infinity" });
+            } else {
+                out.push_str(&format!("{:?}", f));
+            }
+        }
+        ciborium::Value::Text(s) => {
+            out.push('"');
+            for c in s.chars() {
+                match c {
+                    '"' => out.push_str("\\\""),
+                    '\\' => out.push_str("\\\\"),
+                    _ => out.push(c),
+                }
+            }
+            out.push('"');
+        }
+        ciborium::Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        ciborium::Value::Null => out.push_str("null"),
+        ciborium::Value::Tag(tag, inner) => {
+            out.push_str(&tag.to_string());
+            out.push('(');
+            diag(inner, out);
+            out.push(')');
+        }
+        ciborium::Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                diag(item, out);
+            }
+            out.push(']');
+        }
+        ciborium::Value::Map(entries) => {
+            out.push('{');
+            for (i, (key, val)) in entries.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                diag(key, out);
+                out.push_str(": ");
+                diag(val, out);
+            }
+            out.push('}');
+        }
+        // `ciborium::Value` has no variant for `undefined` or other simple
+        // values: `undefined` decodes to `Value::Null` above (so it prints
+        // identically to `null`), and any other unassigned simple value
+        // fails CBOR decoding before a `Value` is ever produced. This arm
+        // is unreachable today; it exists only because the enum is
+        // `#[non_exhaustive]` and must be updated if ciborium adds one.
+        _ => out.push_str("null"),
+    }
+}
+
+pub fn try_cbor2json(cbor: &[u8], no_escape: bool) -> Result<String, CbdError> {
+    let value: ciborium::Value = from_reader(cbor)
+        .map_err(|e| CbdError::Cbor(format!("Failed to decode CBOR: {}", e)))?;
+    value_to_json_string(&value, no_escape)
+}
+
+fn value_to_json_string(value: &ciborium::Value, no_escape: bool) -> Result<String, CbdError> {
+    let json = if no_escape {
+        serde_json::to_value(value).map_err(CbdError::Json)?
+    } else {
+        escape_cbor_value(value)
+    };
+    serde_json::to_string(&json).map_err(CbdError::Json)
+}
+
+/**
+ * Decode an RFC 8742 CBOR Sequence: repeatedly decode one top-level item at
+ * a time from the remaining input, stopping cleanly at EOF and surfacing a
+ * `CbdError` if a trailing item is truncated.
+ */
+pub fn decode_seq(input: &[u8], no_escape: bool) -> Result<Vec<String>, CbdError> {
+    let mut cursor = std::io::Cursor::new(input);
+    let mut items = Vec::new();
+    while (cursor.position() as usize) < input.len() {
+        let value: ciborium::Value = from_reader(&mut cursor)
+            .map_err(|e| CbdError::Cbor(format!("Failed to decode CBOR sequence item: {}", e)))?;
+        items.push(value_to_json_string(&value, no_escape)?);
+    }
+    Ok(items)
+}
+
+/**
+ * Split a length-delimited frame stream (each item prefixed by a big-endian
+ * u32 byte length) back into individual CBOR items.
+ */
+pub fn split_framed(input: &[u8]) -> Result<Vec<&[u8]>, CbdError> {
+    let mut items = Vec::new();
+    let mut pos = 0;
+    while pos < input.len() {
+        if input.len() - pos < 4 {
+            return Err(CbdError::Cbor("Truncated length-delimited frame".to_string()));
+        }
+        let len = u32::from_be_bytes(input[pos..pos + 4].try_into().unwrap()) as usize;
+        pos += 4;
+        if input.len() - pos < len {
+            return Err(CbdError::Cbor("Truncated length-delimited frame".to_string()));
+        }
+        items.push(&input[pos..pos + len]);
+        pos += len;
+    }
+    Ok(items)
+}
+
+pub fn write_framed(writer: &mut impl Write, item: &[u8]) -> Result<(), CbdError> {
+    writer
+        .write_all(&(item.len() as u32).to_be_bytes())
+        .map_err(CbdError::Io)?;
+    writer.write_all(item).map_err(CbdError::Io)
+}
+
+/// Reserved JSON keys used to losslessly round-trip CBOR constructs that
+/// have no native JSON representation.
+const BYTES_KEY: &str = "$bytes";
+const TAG_KEY: &str = "$tag";
+const TAG_VALUE_KEY: &str = "$value";
+const FLOAT_KEY: &str = "$float";
+
+/**
+ * Recursively convert a `ciborium::Value` into a `serde_json::Value`,
+ * escaping CBOR constructs JSON cannot represent natively (byte strings,
+ * tags, non-finite floats) into reserved objects: `{"$bytes": ..}`,
+ * `{"$tag": .., "$value": ..}` and `{"$float": ..}`.
+ */
+fn escape_cbor_value(value: &ciborium::Value) -> serde_json::Value {
+    match value {
+        ciborium::Value::Bytes(bytes) => json!({ BYTES_KEY: base64_encode(bytes) }),
+        ciborium::Value::Tag(tag, inner) => {
+            json!({ TAG_KEY: tag, TAG_VALUE_KEY: escape_cbor_value(inner) })
+        }
+        ciborium::Value::Float(f) if !f.is_finite() => {
+            let label = if f.is_nan() {
+                "NaN"
+            } else if f.is_sign_positive() {
+                "Infinity"
+            } else {
+                "-Infinity"
+            };
+            json!({ FLOAT_KEY: label })
+        }
+        ciborium::Value::Float(f) => json!(f),
+        ciborium::Value::Integer(i) => integer_to_json(i),
+        ciborium::Value::Text(s) => json!(s),
+        ciborium::Value::Bool(b) => json!(b),
+        ciborium::Value::Null => serde_json::Value::Null,
+        ciborium::Value::Array(items) => {
+            serde_json::Value::Array(items.iter().map(escape_cbor_value).collect())
+        }
+        ciborium::Value::Map(entries) => {
+            let mut map = serde_json::Map::with_capacity(entries.len());
+            for (key, val) in entries {
+                map.insert(cbor_map_key_to_json(key), escape_cbor_value(val));
+            }
+            serde_json::Value::Object(map)
+        }
+        _ => serde_json::Value::Null,
+    }
+}
+
+fn integer_to_json(i: &Integer) -> serde_json::Value {
+    if let Ok(n) = i64::try_from(*i) {
+        json!(n)
+    } else if let Ok(n) = u64::try_from(*i) {
+        json!(n)
+    } else {
+        json!(i128::from(*i) as f64)
+    }
+}
+
+fn cbor_map_key_to_json(key: &ciborium::Value) -> String {
+    match key {
+        ciborium::Value::Text(s) => s.clone(),
+        other => escape_cbor_value(other).to_string(),
+    }
+}
+
+/**
+ * Recognize a reserved escape object (exactly the reserved key(s) and
+ * nothing else) and rebuild the `ciborium::Value` it stands for.
+ */
+fn unescape_json_value(value: &serde_json::Value) -> ciborium::Value {
+    if let serde_json::Value::Object(map) = value {
+        if map.len() == 1 {
+            if let Some(serde_json::Value::String(encoded)) = map.get(BYTES_KEY) {
+                if let Ok(bytes) = general_purpose::URL_SAFE_NO_PAD.decode(encoded) {
+                    return ciborium::Value::Bytes(bytes);
+                }
+            }
+            if let Some(serde_json::Value::String(label)) = map.get(FLOAT_KEY) {
+                let float = match label.as_str() {
+                    "NaN" => Some(f64::NAN),
+                    "Infinity" => Some(f64::INFINITY),
+                    "-Infinity" => Some(f64::NEG_INFINITY),
+                    _ => None,
+                };
+                if let Some(float) = float {
+                    return ciborium::Value::Float(float);
+                }
+            }
+        }
+        if map.len() == 2 {
+            if let (Some(tag), Some(inner)) = (map.get(TAG_KEY), map.get(TAG_VALUE_KEY)) {
+                if let Some(tag) = tag.as_u64() {
+                    return ciborium::Value::Tag(tag, Box::new(unescape_json_value(inner)));
+                }
+            }
+        }
+    }
+    json_to_cbor_value(value)
+}
+
+fn json_to_cbor_value(value: &serde_json::Value) -> ciborium::Value {
+    match value {
+        serde_json::Value::Null => ciborium::Value::Null,
+        serde_json::Value::Bool(b) => ciborium::Value::Bool(*b),
+        serde_json::Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                ciborium::Value::Integer(i.into())
+            } else if let Some(u) = n.as_u64() {
+                ciborium::Value::Integer(u.into())
+            } else {
+                ciborium::Value::Float(n.as_f64().unwrap_or(0.0))
+            }
+        }
+        serde_json::Value::String(s) => ciborium::Value::Text(s.clone()),
+        serde_json::Value::Array(items) => {
+            ciborium::Value::Array(items.iter().map(unescape_json_value).collect())
+        }
+        serde_json::Value::Object(map) => ciborium::Value::Map(
+            map.iter()
+                .map(|(k, v)| (ciborium::Value::Text(k.clone()), unescape_json_value(v)))
+                .collect(),
+        ),
+    }
+}
+
+/// Encode a JSON string to CBOR bytes. With `no_escape`, the reserved
+/// escape objects are passed straight through rather than rebuilt into
+/// `Value::Bytes`/`Value::Tag`/non-finite floats.
+pub fn encode(json: &str, no_escape: bool) -> Result<Vec<u8>, CbdError> {
+    let value: serde_json::Value = serde_json::from_str(json).map_err(CbdError::Json)?;
+    let mut writer = Vec::new();
+    if no_escape {
+        into_writer(&value, &mut writer)
+            .map_err(|e| CbdError::Cbor(format!("Failed to encode CBOR: {}", e)))?;
+    } else {
+        let cbor_value = unescape_json_value(&value);
+        into_writer(&cbor_value, &mut writer)
+            .map_err(|e| CbdError::Cbor(format!("Failed to encode CBOR: {}", e)))?;
+    }
+    Ok(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON_IN: &str = r#"[{"key1":"value1","key2":"value2"},{"foo":"bar"},true,false,0,1.0]"#;
+
+    #[test]
+    fn test_cbor2json() {
+        let cbor = vec![161, 97, 107, 97, 118];
+        let json = try_cbor2json(&cbor, false).unwrap();
+        assert_eq!(json, r#"{"k":"v"}"#);
+    }
+
+    #[test]
+    fn test_json2cbor() {
+        let json = r#"{"k":"v"}"#;
+        let cbor = encode(json, false).unwrap();
+        assert_eq!(cbor, vec![161, 97, 107, 97, 118]);
+    }
+
+    #[test]
+    fn test_decode_cbor() {
+        let cbor = encode(JSON_IN, false).unwrap();
+        let json = decode(&cbor, false).unwrap();
+        assert_eq!(json, JSON_IN);
+    }
+
+    #[test]
+    fn test_decode_base64_standard() {
+        let base64 = general_purpose::STANDARD.encode(encode(JSON_IN, false).unwrap());
+        let bytes = dbg!(base64).as_bytes().to_vec();
+        let json_out = decode(&bytes, false).unwrap();
+        assert_eq!(JSON_IN, json_out);
+    }
+
+    #[test]
+    fn test_decode_base64_standard_no_pad() {
+        let base64 = general_purpose::STANDARD_NO_PAD.encode(encode(JSON_IN, false).unwrap());
+        let bytes = dbg!(base64).as_bytes().to_vec();
+        let json_out = decode(&bytes, false).unwrap();
+        assert_eq!(JSON_IN, json_out);
+    }
+
+    #[test]
+    fn test_decode_base64_url_safe() {
+        let base64 = general_purpose::URL_SAFE.encode(encode(JSON_IN, false).unwrap());
+        let bytes = dbg!(base64).as_bytes().to_vec();
+        let json_out = decode(&bytes, false).unwrap();
+        assert_eq!(JSON_IN, json_out);
+    }
+
+    #[test]
+    fn test_decode_base64_url_safe_no_pad() {
+        let base64 = general_purpose::URL_SAFE_NO_PAD.encode(encode(JSON_IN, false).unwrap());
+        let bytes = dbg!(base64).as_bytes().to_vec();
+        let json_out = decode(&bytes, false).unwrap();
+        assert_eq!(JSON_IN, json_out);
+    }
+
+    #[test]
+    fn test_escape_bytes_round_trip() {
+        let json_in = r#"{"$bytes":"AQIDBA"}"#;
+        let cbor = encode(json_in, false).unwrap();
+        let json_out = try_cbor2json(&cbor, false).unwrap();
+        assert_eq!(json_in, json_out);
+    }
+
+    #[test]
+    fn test_escape_tag_round_trip() {
+        let json_in = r#"{"$tag":6,"$value":"hello"}"#;
+        let cbor = encode(json_in, false).unwrap();
+        let json_out = try_cbor2json(&cbor, false).unwrap();
+        assert_eq!(json_in, json_out);
+    }
+
+    #[test]
+    fn test_escape_non_finite_float_round_trip() {
+        let json_in = r#"{"$float":"NaN"}"#;
+        let cbor = encode(json_in, false).unwrap();
+        let json_out = try_cbor2json(&cbor, false).unwrap();
+        assert_eq!(json_in, json_out);
+    }
+
+    #[test]
+    fn test_decode_seq() {
+        let mut cbor = encode(r#"{"k":"v"}"#, false).unwrap();
+        cbor.extend(encode("true", false).unwrap());
+        cbor.extend(encode("1", false).unwrap());
+        let items = decode_seq(&cbor, false).unwrap();
+        assert_eq!(items, vec![r#"{"k":"v"}"#, "true", "1"]);
+    }
+
+    #[test]
+    fn test_decode_seq_truncated_trailing_item() {
+        let mut cbor = encode(r#"{"k":"v"}"#, false).unwrap();
+        cbor.push(0xa1);
+        assert!(decode_seq(&cbor, false).is_err());
+    }
+
+    #[test]
+    fn test_split_framed_round_trip() {
+        let items: Vec<Vec<u8>> = vec![
+            encode(r#"{"k":"v"}"#, false).unwrap(),
+            encode("true", false).unwrap(),
+        ];
+        let mut framed = Vec::new();
+        for item in &items {
+            write_framed(&mut framed, item).unwrap();
+        }
+        let split = split_framed(&framed).unwrap();
+        assert_eq!(split, items.iter().map(|i| i.as_slice()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_split_framed_truncated() {
+        let mut framed = Vec::new();
+        write_framed(&mut framed, &encode(r#""v""#, false).unwrap()).unwrap();
+        framed.truncate(framed.len() - 1);
+        assert!(split_framed(&framed).is_err());
+    }
+
+    #[test]
+    fn test_decode_base64_not_hijacked_by_multibase_prefix() {
+        // An array of 24+ items encodes with a leading 0x98 byte, which
+        // base64url-encodes to a leading 'm' — the multibase prefix for
+        // plain base64. Plain base64 input like this must still decode as
+        // base64, not be misread as multibase.
+        let json_in: String = format!(
+            "[{}]",
+            (0..24).map(|n| n.to_string()).collect::<Vec<_>>().join(",")
+        );
+        let cbor = encode(&json_in, false).unwrap();
+        let text = base64_encode(&cbor);
+        assert!(text.starts_with('m'));
+        let json_out = decode(text.as_bytes(), false).unwrap();
+        assert_eq!(json_in, json_out);
+    }
+
+    #[test]
+    fn test_decode_multibase_hex() {
+        let cbor = encode(JSON_IN, false).unwrap();
+        let text = format!("f{}", hex::encode(&cbor));
+        let json_out = decode(text.as_bytes(), false).unwrap();
+        assert_eq!(JSON_IN, json_out);
+    }
+
+    #[test]
+    fn test_decode_multibase_base32() {
+        let cbor = encode(JSON_IN, false).unwrap();
+        let text = multibase_encode(&cbor, Base::Base32);
+        let json_out = decode(text.as_bytes(), false).unwrap();
+        assert_eq!(JSON_IN, json_out);
+    }
+
+    #[test]
+    fn test_decode_multibase_base58btc() {
+        let cbor = encode(JSON_IN, false).unwrap();
+        let text = multibase_encode(&cbor, Base::Base58btc);
+        let json_out = decode(text.as_bytes(), false).unwrap();
+        assert_eq!(JSON_IN, json_out);
+    }
+
+    #[test]
+    fn test_decode_multibase_base64url() {
+        let cbor = encode(JSON_IN, false).unwrap();
+        let text = multibase_encode(&cbor, Base::Base64url);
+        let json_out = decode(text.as_bytes(), false).unwrap();
+        assert_eq!(JSON_IN, json_out);
+    }
+
+    #[test]
+    fn test_decode_multibase_short_payload_round_trip() {
+        // A long fixture like JSON_IN masks base64/multibase collisions
+        // because the wrong-alphabet reinterpretation tends to fail CBOR
+        // decoding outright. Short payloads are exactly where a colliding
+        // decode can still look like valid (if wrong) CBOR, e.g. base58btc
+        // `z`-prefixed `{"k":"v"}` misread as plain base64. Every `--base`
+        // value must round-trip short input too.
+        let short_json = r#"{"k":"v"}"#;
+        let cbor = encode(short_json, false).unwrap();
+        for base in [
+            Base::Hex,
+            Base::Base32,
+            Base::Base58btc,
+            Base::Base64url,
+            Base::Base64,
+        ] {
+            let text = multibase_encode(&cbor, base);
+            let json_out = decode(text.as_bytes(), false).unwrap();
+            assert_eq!(short_json, json_out, "round-trip failed for {:?}", base);
+        }
+    }
+
+    #[test]
+    fn test_armor_round_trip() {
+        let cbor = encode(JSON_IN, false).unwrap();
+        let armored = armor_encode(&cbor);
+        assert!(armored.starts_with(ARMOR_BEGIN));
+        let json_out = decode(armored.as_bytes(), false).unwrap();
+        assert_eq!(JSON_IN, json_out);
+    }
+
+    #[test]
+    fn test_armor_decode_with_header_block() {
+        let cbor = encode(JSON_IN, false).unwrap();
+        let armored = armor_encode(&cbor);
+        let with_header = armored.replacen(
+            &format!("{}\n", ARMOR_BEGIN),
+            &format!("{}\nContent-Type: application/cbor\n\n", ARMOR_BEGIN),
+            1,
+        );
+        let json_out = decode(with_header.as_bytes(), false).unwrap();
+        assert_eq!(JSON_IN, json_out);
+    }
+
+    #[test]
+    fn test_cbor_to_json_and_json_to_cbor() {
+        let cbor = json_to_cbor(r#"{"k":"v"}"#).unwrap();
+        let json = cbor_to_json(&cbor).unwrap();
+        assert_eq!(json, r#"{"k":"v"}"#);
+    }
+
+    #[test]
+    fn test_decode_diag_bytes() {
+        let cbor = encode(r#"{"$bytes":"3q2-7w"}"#, false).unwrap();
+        assert_eq!(decode_diag(&cbor).unwrap(), "h'deadbeef'");
+    }
+
+    #[test]
+    fn test_decode_diag_tag() {
+        let cbor = encode(r#"{"$tag":6,"$value":"hello"}"#, false).unwrap();
+        assert_eq!(decode_diag(&cbor).unwrap(), r#"6("hello")"#);
+    }
+
+    #[test]
+    fn test_decode_diag_map_and_array() {
+        let cbor = encode(JSON_IN, false).unwrap();
+        let diag = decode_diag(&cbor).unwrap();
+        assert_eq!(
+            diag,
+            r#"[{"key1": "value1", "key2": "value2"}, {"foo": "bar"}, true, false, 0, 1.0]"#
+        );
+    }
+
+    #[test]
+    fn test_decode_diag_non_finite_float() {
+        let cbor = encode(r#"{"$float":"NaN"}"#, false).unwrap();
+        assert_eq!(decode_diag(&cbor).unwrap(), "NaN");
+    }
+
+    #[test]
+    fn test_decode_diag_undefined() {
+        // `ciborium::Value` has no `undefined` variant, so CBOR `undefined`
+        // (simple value 23, 0xf7) decodes to `Value::Null` and prints the
+        // same as `null`.
+        let cbor = vec![0xf7];
+        assert_eq!(decode_diag(&cbor).unwrap(), "null");
+    }
+}
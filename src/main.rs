@@ -1,42 +1,36 @@
-use std::fmt::{Debug, Display, Formatter};
+//! # Compact Binary Decoder #
+//!
+//! CLI for decoding and encoding compact binary (cbor) data. See the `cbd`
+//! library crate for the underlying conversion API.
+//!
+//! ## Usage ##
+//!
+//! Decode CBOR from stdin and output JSON:
+//! ```shell
+//! $ cat file.cbor | cbd
+//! {"key":"value"}
+//! ```
+//!
+//! Encode JSON from stdin and output CBOR:
+//! ```shell
+//! $ cat file.json | cbd -e
+//! ?ckeyevalue%
+//! ```
+//!
+//! ## Installation ##
+//! ```shell
+//! $cargo install cbd
+//! ```
+
 use std::io::{Read, Write};
 use clap::Parser;
-use ciborium::from_reader;
-use ciborium::into_writer;
-use base64::engine::general_purpose;
-use base64::Engine;
-
-/**
- * # Compact Binary Decoder #
- *
- * Library for decoding and encoding compact binary (cbor) data
- *
- * ## Usage ##
- *
- * Decode CBOR from stdin and output JSON:
- * ```shell
- * $ cat file.cbor | cbd
- * {"key": "value"}
- * ```
- *
- * Encode JSON from stdin and output CBOR:
- * ```shell
- * $ cat file.json | cbd -e
- * ?ckeyevalue%
- * ```
- *
- * ## Installation ##
- * ```shell
- * $cargo install cbd
- * ```
- */
+use cbd::{Base, CbdError};
 
 fn main() {
     let cli = Cli::parse();
-    if cli.encode {
-        cbor_encode(cli.base64);
-    } else {
-        cbor_decode();
+    if let Err(e) = run(cli) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
     }
 }
 
@@ -47,159 +41,147 @@ struct Cli {
 
     #[clap(short, long)]
     base64: bool,
-}
-
-fn try_base64_decode(input: &Vec<u8>) -> Result<Vec<u8>, CbdError> {
-    let text = std::str::from_utf8(input).map_err(|e| CbdError {
-        message: "Failed to decode input as utf8".to_string(),
-        source: Some(Box::new(e)),
-    })?.trim_end();
-    if let Ok(bytes) = general_purpose::URL_SAFE_NO_PAD.decode(text) {
-        return Ok(bytes)
-    }
-    if let Ok(bytes) = general_purpose::STANDARD.decode(text) {
-        return Ok(bytes)
-    }
-    if let Ok(bytes) = general_purpose::URL_SAFE.decode(text) {
-        return Ok(bytes)
-    }
-    if let Ok(bytes) = general_purpose::STANDARD_NO_PAD.decode(text) {
-        return Ok(bytes)
-    }
-    Err(CbdError {
-        message: "Failed to decode base64".to_string(),
-        source: None,
-    })
-}
 
-#[derive(Debug)]
-struct CbdError {
-    message: String,
-    source: Option<Box<dyn std::error::Error>>,
+    /// Disable the tagged-JSON escape scheme for CBOR byte strings, tags and
+    /// non-finite floats, keeping the old lossy CBOR<->JSON mapping.
+    #[clap(long)]
+    no_escape: bool,
+
+    /// Decode/encode a CBOR Sequence (RFC 8742) of concatenated top-level
+    /// items instead of exactly one, as JSON Lines.
+    #[clap(long)]
+    seq: bool,
+
+    /// Like --seq, but each item is prefixed with its length so item
+    /// boundaries don't require parsing CBOR to find.
+    #[clap(long)]
+    framed: bool,
+
+    /// Emit multibase-prefixed text in the given base instead of raw CBOR
+    /// bytes (or plain base64 when --base64 is set).
+    #[clap(long, value_enum)]
+    base: Option<Base>,
+
+    /// Wrap the base64 body in a PEM-like "-----BEGIN CBOR-----" /
+    /// "-----END CBOR-----" armor, safe to paste into emails and diffs.
+    #[clap(long)]
+    armor: bool,
+
+    /// Decode to RFC 8949 extended diagnostic notation instead of JSON.
+    /// Unassigned CBOR simple values (e.g. `simple(255)`) aren't supported:
+    /// ciborium can't produce them, so such input fails to decode rather
+    /// than printing `simple(n)`.
+    #[clap(long)]
+    diag: bool,
 }
 
-impl Display for CbdError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.message)
+fn run(cli: Cli) -> Result<(), CbdError> {
+    if cli.encode {
+        if cli.framed {
+            cbor_encode_framed(cli.no_escape)
+        } else if cli.seq {
+            cbor_encode_seq(cli.no_escape)
+        } else {
+            cbor_encode(cli.base64, cli.base, cli.armor, cli.no_escape)
+        }
+    } else if cli.framed {
+        cbor_decode_framed(cli.no_escape)
+    } else if cli.seq {
+        cbor_decode_seq(cli.no_escape)
+    } else if cli.diag {
+        cbor_decode_diag()
+    } else {
+        cbor_decode(cli.no_escape)
     }
 }
 
-impl std::error::Error for CbdError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        self.source.as_deref()
-    }
+fn read_stdin_bytes() -> Result<Vec<u8>, CbdError> {
+    let mut buffer = Vec::new();
+    std::io::stdin()
+        .read_to_end(&mut buffer)
+        .map_err(CbdError::Io)?;
+    Ok(buffer)
 }
 
-fn base64_encode(input: &[u8]) -> String {
-    general_purpose::URL_SAFE_NO_PAD.encode(input)
+fn read_stdin_string() -> Result<String, CbdError> {
+    let mut buffer = String::new();
+    std::io::stdin()
+        .read_to_string(&mut buffer)
+        .map_err(CbdError::Io)?;
+    Ok(buffer)
 }
 
-fn cbor_decode() {
-    let mut buffer = Vec::new();
-    std::io::stdin().read_to_end(&mut buffer).expect("Failed to read from stdin");
-    let json = decode(&buffer).expect("Failed to decode CBOR");
+fn cbor_decode(no_escape: bool) -> Result<(), CbdError> {
+    let buffer = read_stdin_bytes()?;
+    let json = cbd::decode(&buffer, no_escape)?;
     println!("{}", json);
+    Ok(())
 }
 
-/**
- * Decode input to JSON
- * Try base64 encoded cbor first, then raw cbor
- */
-fn decode(input: &Vec<u8>) -> Result<String, CbdError> {
-    if let Ok(cbor) = try_base64_decode(input) {
-        try_cbor2json(&cbor)
-    } else {
-        try_cbor2json(input)
+fn cbor_decode_diag() -> Result<(), CbdError> {
+    let buffer = read_stdin_bytes()?;
+    println!("{}", cbd::decode_diag(&buffer)?);
+    Ok(())
+}
+
+fn cbor_decode_seq(no_escape: bool) -> Result<(), CbdError> {
+    let buffer = read_stdin_bytes()?;
+    for item in cbd::decode_seq(&buffer, no_escape)? {
+        println!("{}", item);
     }
+    Ok(())
 }
 
-fn try_cbor2json(cbor: &Vec<u8>) -> Result<String, CbdError> {
-    let value: ciborium::Value = from_reader(&cbor[..]).map_err(|e| CbdError {
-        message: "Failed to decode CBOR".to_string(),
-        source: Some(Box::new(e)),
-    })?;
-    serde_json::to_string(&value).map_err(|e| CbdError {
-        message: "Failed to encode JSON".to_string(),
-        source: Some(Box::new(e)),
-    })
+fn cbor_decode_framed(no_escape: bool) -> Result<(), CbdError> {
+    let buffer = read_stdin_bytes()?;
+    for frame in cbd::split_framed(&buffer)? {
+        println!("{}", cbd::try_cbor2json(frame, no_escape)?);
+    }
+    Ok(())
 }
 
-fn cbor_encode(base64: bool) {
-    let mut buffer = String::new();
-    std::io::stdin().read_to_string(&mut buffer).expect("Failed to read from stdin");
-    let json = buffer.trim();
-    let cbor = json2cbor(json);
-    if base64 {
-        let base64 = base64_encode(&cbor);
-        std::io::stdout().write_all(base64.as_bytes()).expect("Failed to write to stdout");
+fn cbor_encode(base64: bool, base: Option<Base>, armor: bool, no_escape: bool) -> Result<(), CbdError> {
+    let buffer = read_stdin_string()?;
+    let cbor = cbd::encode(buffer.trim(), no_escape)?;
+    let mut stdout = std::io::stdout();
+    if let Some(base) = base {
+        stdout.write_all(cbd::multibase_encode(&cbor, base).as_bytes())
+    } else if armor {
+        stdout.write_all(cbd::armor_encode(&cbor).as_bytes())
+    } else if base64 {
+        stdout.write_all(cbd::base64_encode(&cbor).as_bytes())
     } else {
-        std::io::stdout().write_all(&cbor).expect("Failed to write to stdout");
+        stdout.write_all(&cbor)
     }
+    .map_err(CbdError::Io)
 }
 
-fn json2cbor(json: &str) -> Vec<u8> {
-    let value: serde_json::Value = serde_json::from_str(json).expect("Failed to decode JSON");
-    let mut writer = Vec::new();
-    into_writer(&value, &mut writer).expect("Failed to encode CBOR");
-    writer
+/**
+ * Read newline-delimited JSON from stdin, encode each line to CBOR, and
+ * concatenate the results into a single CBOR Sequence.
+ */
+fn cbor_encode_seq(no_escape: bool) -> Result<(), CbdError> {
+    let buffer = read_stdin_string()?;
+    let mut stdout = std::io::stdout();
+    for line in buffer.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        stdout.write_all(&cbd::encode(line, no_escape)?).map_err(CbdError::Io)?;
+    }
+    Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    const JSON_IN: &str = r#"[{"key1":"value1","key2":"value2"},{"foo":"bar"},true,false,0,1.0]"#;
-
-    #[test]
-    fn test_cbor2json() {
-        let cbor = vec![161, 97, 107, 97, 118];
-        let json = try_cbor2json(&cbor).unwrap();
-        assert_eq!(json, r#"{"k":"v"}"#);
-    }
-
-    #[test]
-    fn test_json2cbor() {
-        let json = r#"{"k":"v"}"#;
-        let cbor = json2cbor(json);
-        assert_eq!(cbor, vec![161, 97, 107, 97, 118]);
-    }
-
-    #[test]
-    fn test_decode_cbor() {
-        let cbor = json2cbor(JSON_IN);
-        let json = decode(&cbor).unwrap();
-        assert_eq!(json, JSON_IN);
-    }
-
-    #[test]
-    fn test_decode_base64_standard() {
-        let base64 = general_purpose::STANDARD.encode(json2cbor(JSON_IN));
-        let bytes = dbg!(base64).as_bytes().to_vec();
-        let json_out = decode(&bytes).unwrap();
-        assert_eq!(JSON_IN, json_out);
-    }
-
-    #[test]
-    fn test_decode_base64_standard_no_pad() {
-        let base64 = general_purpose::STANDARD_NO_PAD.encode(json2cbor(JSON_IN));
-        let bytes = dbg!(base64).as_bytes().to_vec();
-        let json_out = decode(&bytes).unwrap();
-        assert_eq!(JSON_IN, json_out);
-    }
-
-    #[test]
-    fn test_decode_base64_url_safe() {
-        let base64 = general_purpose::URL_SAFE.encode(json2cbor(JSON_IN));
-        let bytes = dbg!(base64).as_bytes().to_vec();
-        let json_out = decode(&bytes).unwrap();
-        assert_eq!(JSON_IN, json_out);
-    }
-
-    #[test]
-    fn test_decode_base64_url_safe_no_pad() {
-        let base64 = general_purpose::URL_SAFE_NO_PAD.encode(json2cbor(JSON_IN));
-        let bytes = dbg!(base64).as_bytes().to_vec();
-        let json_out = decode(&bytes).unwrap();
-        assert_eq!(JSON_IN, json_out);
-    }
+fn cbor_encode_framed(no_escape: bool) -> Result<(), CbdError> {
+    let buffer = read_stdin_string()?;
+    let mut stdout = std::io::stdout();
+    for line in buffer.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        cbd::write_framed(&mut stdout, &cbd::encode(line, no_escape)?)?;
+    }
+    Ok(())
 }